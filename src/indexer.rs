@@ -1,16 +1,37 @@
-use std::{fs::File, path::Path, time::Duration};
-
-use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use md4::{Digest, Md4};
 use memmap::Mmap;
+use clap::ValueEnum;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use ranidb::AniDb;
 use rayon::{iter::ParallelIterator, slice::ParallelSlice};
-use rusqlite::params;
+use rusqlite::{params, OptionalExtension};
+use serde::Serialize;
 use tokio::fs;
 
 const ED2K_CHUNK_SIZE: usize = 9728000;
 
+/// Minimum time between requests sent to AniDB, to stay well clear of the UDP
+/// API's flood ban.
+const MIN_ANIDB_REQUEST_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Ceiling for the exponential backoff applied after a flood/5xx response
+/// from AniDB, so a struggling session still makes slow progress instead of
+/// backing off forever.
+const MAX_ANIDB_BACKOFF: Duration = Duration::from_secs(120);
+
+/// How long a path must sit untouched in the pending set before we act on it.
+/// Editors and downloaders often fire several events in a row for one file.
+const WATCH_DEBOUNCE: Duration = Duration::from_secs(5);
+
 fn ed2k_hash(file: &File, pb: &ProgressBar) -> std::io::Result<[u8; 16]> {
     let map = unsafe { Mmap::map(file) }?;
 
@@ -28,6 +49,27 @@ fn ed2k_hash(file: &File, pb: &ProgressBar) -> std::io::Result<[u8; 16]> {
     Ok(root_hash.into())
 }
 
+fn path_size(path: &Path) -> i64 {
+    path.metadata()
+        .map(|f| {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                f.size() as i64
+            }
+
+            #[cfg(windows)]
+            {
+                use std::os::windows::fs::MetadataExt;
+                f.file_size() as i64
+            }
+
+            #[cfg(not(any(unix, windows)))]
+            -1
+        })
+        .unwrap_or_default()
+}
+
 fn init_database(db_path: &Path) -> Result<rusqlite::Connection> {
     let conn = rusqlite::Connection::open(db_path).context("failed to open db")?;
 
@@ -144,12 +186,91 @@ fn init_database(db_path: &Path) -> Result<rusqlite::Connection> {
         params![],
     )?;
 
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS file_status (
+            path                TEXT PRIMARY KEY,
+            status              TEXT NOT NULL,
+            size                INTEGER,
+            ed2k                TEXT,
+            error               TEXT
+        )",
+        params![],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS mylist (
+            lid                 INTEGER PRIMARY KEY,
+            fid                 INTEGER,
+            eid                 INTEGER,
+            aid                 INTEGER,
+            gid                 INTEGER,
+            date                INTEGER,
+            state               INTEGER,
+            viewdate            INTEGER,
+            storage             TEXT,
+            source              TEXT,
+            other               TEXT,
+            filestate           INTEGER,
+
+            UNIQUE (fid) ON CONFLICT REPLACE,
+            FOREIGN KEY (fid) REFERENCES files (fid)
+        )",
+        params![],
+    )?;
+
     Ok(conn)
 }
 
+/// Wraps the raw AniDB session and tracks when the last request went out, so
+/// `pace` can enforce the protocol's minimum inter-packet delay. Every
+/// `CachedFacade` method calls `pace` before its live request, so a burst of
+/// cache misses (file, anime, episode, group, mylist) can never fire
+/// back-to-back. Calls made directly through `Deref` (`auth`, `logout`) are
+/// not paced.
+struct PacedAniDb {
+    inner: AniDb,
+    last_request: Option<Instant>,
+}
+
+impl PacedAniDb {
+    fn new(inner: AniDb) -> Self {
+        Self { inner, last_request: None }
+    }
+
+    async fn pace(&mut self) {
+        if let Some(last) = self.last_request {
+            let elapsed = last.elapsed();
+            if elapsed < MIN_ANIDB_REQUEST_INTERVAL {
+                tokio::time::sleep(MIN_ANIDB_REQUEST_INTERVAL - elapsed).await;
+            }
+        }
+        self.last_request = Some(Instant::now());
+    }
+}
+
+impl std::ops::Deref for PacedAniDb {
+    type Target = AniDb;
+
+    fn deref(&self) -> &AniDb {
+        &self.inner
+    }
+}
+
+impl std::ops::DerefMut for PacedAniDb {
+    fn deref_mut(&mut self) -> &mut AniDb {
+        &mut self.inner
+    }
+}
+
+/// Holds the db connection as a cloned `Arc` rather than a borrowed
+/// reference so each method can lock it just for its own query/insert and
+/// drop the guard before the paced, potentially multi-second AniDB `.await`
+/// in between — otherwise a caller holding the lock across the whole method
+/// call would serialize every other lock-holder (e.g. the hashing workers'
+/// `set_file_status`) behind AniDB's pacing.
 struct CachedFacade<'a> {
-    anidb: &'a mut AniDb,
-    conn: &'a mut rusqlite::Connection,
+    anidb: &'a mut PacedAniDb,
+    conn: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>,
 }
 
 macro_rules! simple_cache {
@@ -159,19 +280,21 @@ macro_rules! simple_cache {
         $($field:ident,)*
     ) => {
         async fn $funname(&mut self, id: u32) -> Result<ranidb::$funret> {
-            let cached =
-                self.conn
-                    .query_row(
-                        concat!("SELECT * FROM ", $tablename, " WHERE ", $idx, " = ?;"),
-                        &[&id],
-
-                        #[allow(unused_assignments)] // last `n` increment is unused
-                        |row| {
-                            let mut n = 0;
-                            Ok(ranidb::$funret {
-                                $( $field: row.get({ let ret = n; n += 1; ret })?, )*
-                            })
-                        });
+            let cached = self
+                .conn
+                .lock()
+                .unwrap()
+                .query_row(
+                    concat!("SELECT * FROM ", $tablename, " WHERE ", $idx, " = ?;"),
+                    &[&id],
+
+                    #[allow(unused_assignments)] // last `n` increment is unused
+                    |row| {
+                        let mut n = 0;
+                        Ok(ranidb::$funret {
+                            $( $field: row.get({ let ret = n; n += 1; ret })?, )*
+                        })
+                    });
 
             if let Ok(hit) = cached {
                 log::debug!("found in cache");
@@ -179,6 +302,8 @@ macro_rules! simple_cache {
                 Ok(hit)
             }
             else {
+                self.anidb.pace().await;
+
                 let live = self
                     .anidb
                     .$ranidbfun(id)
@@ -186,6 +311,8 @@ macro_rules! simple_cache {
                     .context("failed to get info")?;
 
                 self.conn
+                    .lock()
+                    .unwrap()
                     .execute(
                         concat!("INSERT OR REPLACE INTO ", $tablename, " VALUES ", $questionmarks),
                         params![
@@ -201,7 +328,7 @@ macro_rules! simple_cache {
 }
 
 impl<'a> CachedFacade<'a> {
-    fn new(anidb: &'a mut AniDb, conn: &'a mut rusqlite::Connection) -> Self {
+    fn new(anidb: &'a mut PacedAniDb, conn: std::sync::Arc<std::sync::Mutex<rusqlite::Connection>>) -> Self {
         Self { anidb, conn }
     }
 
@@ -230,6 +357,8 @@ impl<'a> CachedFacade<'a> {
     async fn get_file(&mut self, path: &Path, pb: &ProgressBar) -> Result<Option<ranidb::File>> {
         let fid: Option<(u32, String)> = self
             .conn
+            .lock()
+            .unwrap()
             .query_row(
                 "SELECT fid, path FROM indexed_files WHERE path = ? OR (filename = ? AND filesize = ?);",
                 params![
@@ -266,6 +395,8 @@ impl<'a> CachedFacade<'a> {
 
             if indexed_path != path.to_string_lossy() {
                 self.conn
+                    .lock()
+                    .unwrap()
                     .execute("UPDATE indexed_files SET path = ? WHERE path = ?", params![
                         &path.to_string_lossy(),
                         &indexed_path
@@ -275,6 +406,8 @@ impl<'a> CachedFacade<'a> {
 
             Ok(self
                 .conn
+                .lock()
+                .unwrap()
                 .query_row("SELECT * FROM files WHERE fid = ?;", [fid], |row| {
                     Ok(ranidb::File {
                         fid: row.get(0)?,
@@ -311,88 +444,282 @@ impl<'a> CachedFacade<'a> {
                 u128::from_be_bytes(ed2k_hash(&file, pb).context("failed to hash")?)
             );
 
-            let file = match self.anidb.file_by_ed2k(size, &ed2k).await {
-                Ok(file) => file,
-                Err(ranidb::Error::AniDb(ranidb::responses::Error::Other(320, _))) => {
-                    return Ok(None)
-                }
-                e => panic!("failed to get file info: {:?}", e),
-            };
+            self.resolve_and_cache(path, size, &ed2k).await
+        }
+    }
 
-            self.conn
-                .execute(
-                    "INSERT OR REPLACE INTO files VALUES
-                        (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-                    params![
-                        &file.fid,
-                        &file.aid,
-                        &file.eid,
-                        &file.gid,
-                        &file.state,
-                        &file.size,
-                        &file.ed2k,
-                        &file.colour_depth,
-                        &file.quality,
-                        &file.source,
-                        &file.audio_codec_list,
-                        &file.audio_bitrate_list,
-                        &file.video_codec,
-                        &file.video_bitrate,
-                        &file.video_resolution,
-                        &file.dub_language,
-                        &file.sub_language,
-                        &file.length_in_seconds,
-                        &file.description,
-                        &file.aired_date,
-                    ],
-                )
-                .expect("failed to store file");
+    /// Look up an already-hashed file on AniDB and cache the result, without
+    /// opening or hashing the file (only its size is read back for
+    /// `indexed_files.filesize`). Used both by `get_file` above (which hashes
+    /// the file itself first) and by the concurrent indexing pipeline, whose
+    /// hashing workers compute the ed2k digest ahead of time.
+    async fn resolve_and_cache(
+        &mut self,
+        path: &Path,
+        size: u64,
+        ed2k: &str,
+    ) -> Result<Option<ranidb::File>> {
+        self.anidb.pace().await;
+
+        let file = match self.anidb.file_by_ed2k(size, ed2k).await {
+            Ok(file) => file,
+            Err(ranidb::Error::AniDb(ranidb::responses::Error::Other(320, _))) => return Ok(None),
+            Err(e) => return Err(e).context("failed to get file info"),
+        };
+
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT OR REPLACE INTO files VALUES
+                (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                &file.fid,
+                &file.aid,
+                &file.eid,
+                &file.gid,
+                &file.state,
+                &file.size,
+                &file.ed2k,
+                &file.colour_depth,
+                &file.quality,
+                &file.source,
+                &file.audio_codec_list,
+                &file.audio_bitrate_list,
+                &file.video_codec,
+                &file.video_bitrate,
+                &file.video_resolution,
+                &file.dub_language,
+                &file.sub_language,
+                &file.length_in_seconds,
+                &file.description,
+                &file.aired_date,
+            ],
+        )
+        .context("failed to store file")?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO indexed_files VALUES (?, ?, ?, ?)",
+            params![
+                &path.to_string_lossy(),
+                &path.file_name().unwrap_or_default().to_string_lossy(),
+                path_size(path),
+                &file.fid
+            ],
+        )
+        .context("failed to store indexed file")?;
+
+        Ok(Some(file))
+    }
 
-            self.conn
-                .execute("INSERT OR REPLACE INTO indexed_files VALUES (?, ?, ?, ?)", params![
-                    &path.to_string_lossy(),
-                    &path.file_name().unwrap_or_default().to_string_lossy(),
-                    &path
-                        .metadata()
-                        .map(|f| {
-                            #[cfg(unix)]
-                            {
-                                use std::os::unix::fs::MetadataExt;
-                                f.size() as i64
-                            }
-
-                            #[cfg(windows)]
-                            {
-                                use std::os::windows::fs::MetadataExt;
-                                f.file_size() as i64
-                            }
-
-                            #[cfg(not(any(unix, windows)))]
-                            -1
-                        })
-                        .unwrap_or_default(),
-                    &file.fid
-                ])
-                .expect("failed to store indexed file");
+    /// Register `fid` in AniDB MyList, or return the cached entry if it was
+    /// already added on a previous run.
+    async fn mylist_for_file(
+        &mut self,
+        fid: u32,
+        state: u8,
+        viewed: bool,
+        storage: &str,
+        source: &str,
+    ) -> Result<ranidb::MyListEntry> {
+        let cached = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row("SELECT * FROM mylist WHERE fid = ?;", [fid], |row| {
+                Ok(ranidb::MyListEntry {
+                    lid: row.get(0)?,
+                    fid: row.get(1)?,
+                    eid: row.get(2)?,
+                    aid: row.get(3)?,
+                    gid: row.get(4)?,
+                    date: row.get(5)?,
+                    state: row.get(6)?,
+                    viewdate: row.get(7)?,
+                    storage: row.get(8)?,
+                    source: row.get(9)?,
+                    other: row.get(10)?,
+                    filestate: row.get(11)?,
+                })
+            })
+            .optional()
+            .context("failed to query mylist cache")?;
 
-            Ok(Some(file))
+        if let Some(entry) = cached {
+            log::debug!("mylist entry already cached for fid {fid}");
+            return Ok(entry);
         }
+
+        self.anidb.pace().await;
+
+        let entry = self
+            .anidb
+            .mylist_add(fid, state, viewed, storage, source)
+            .await
+            .context("failed to add to mylist")?;
+
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO mylist VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    &entry.lid,
+                    &entry.fid,
+                    &entry.eid,
+                    &entry.aid,
+                    &entry.gid,
+                    &entry.date,
+                    &entry.state,
+                    &entry.viewdate,
+                    &entry.storage,
+                    &entry.source,
+                    &entry.other,
+                    &entry.filestate,
+                ],
+            )
+            .context("failed to store mylist entry")?;
+
+        Ok(entry)
     }
 }
 
-pub(crate) async fn index(path: &Path, db_path: &Path) -> Result<()> {
-    let mut conn = init_database(db_path)?;
+/// A file that has finished the CPU-bound hashing stage and is ready to be
+/// resolved against AniDB. Carries its own progress bar through the pipeline
+/// so the hashing and resolving stages can share one `MultiProgress` display.
+struct HashedFile {
+    path: PathBuf,
+    size: u64,
+    ed2k: String,
+    pb: ProgressBar,
+}
 
-    let mut anidb = AniDb::new("tetsu", 1);
+/// MyList storage state, mirroring the values AniDB's protocol expects for
+/// `mylistadd`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub(crate) enum MyListState {
+    Internal,
+    External,
+    Deleted,
+}
 
-    anidb
-        .auth("darkwater_", &std::env::var("PASS").unwrap())
-        .await
-        .expect("failed login");
+impl MyListState {
+    fn as_u8(self) -> u8 {
+        match self {
+            MyListState::Internal => 1,
+            MyListState::External => 2,
+            MyListState::Deleted => 3,
+        }
+    }
+}
 
-    log::info!("session key: {}", anidb.session_key().unwrap());
+/// MyList options for the `index` subcommand's opt-in `--add-to-mylist` flag.
+pub(crate) struct MylistOptions {
+    pub(crate) state: MyListState,
+    pub(crate) watched: bool,
+    pub(crate) storage: Option<String>,
+    pub(crate) source: Option<String>,
+}
+
+fn set_file_status(
+    conn: &rusqlite::Connection,
+    path: &Path,
+    status: &str,
+    size: Option<i64>,
+    ed2k: Option<&str>,
+    error: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO file_status (path, status, size, ed2k, error) VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT(path) DO UPDATE SET status = excluded.status, size = excluded.size,
+            ed2k = excluded.ed2k, error = excluded.error;",
+        params![path.to_string_lossy(), status, size, ed2k, error],
+    )?;
+    Ok(())
+}
+
+/// Paths that already reached a terminal state (`resolved` or `not_found`) on
+/// a previous, interrupted run, so this run can skip straight past them.
+fn already_done_paths(conn: &rusqlite::Connection) -> Result<std::collections::HashSet<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT path FROM file_status WHERE status = 'resolved' OR status = 'not_found';",
+    )?;
+    let paths = stmt
+        .query_map(params![], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(paths)
+}
+
+/// Files already present in `indexed_files` from *any* previous run,
+/// looked up the same way `CachedFacade::get_file` matches a file: by exact
+/// path, or by filename+size for a file that moved. Unlike
+/// `already_done_paths`, this also catches files that were resolved via
+/// `watch` or `mylist` (which write `indexed_files` directly without ever
+/// touching `file_status`), so `index` doesn't re-hash and re-query AniDB
+/// for a file it has already resolved.
+struct IndexedFileLookup {
+    by_path: std::collections::HashSet<String>,
+    by_name_size: std::collections::HashSet<(String, i64)>,
+}
 
-    let mut facade = CachedFacade::new(&mut anidb, &mut conn);
+impl IndexedFileLookup {
+    fn load(conn: &rusqlite::Connection) -> Result<Self> {
+        let mut stmt = conn.prepare("SELECT path, filename, filesize FROM indexed_files;")?;
+        let mut by_path = std::collections::HashSet::new();
+        let mut by_name_size = std::collections::HashSet::new();
+
+        let rows = stmt
+            .query_map(params![], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for (path, filename, filesize) in rows {
+            by_path.insert(path);
+            by_name_size.insert((filename, filesize));
+        }
+
+        Ok(Self { by_path, by_name_size })
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        if self.by_path.contains(&path.to_string_lossy().to_string()) {
+            return true;
+        }
+
+        let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        self.by_name_size.contains(&(filename, path_size(path)))
+    }
+}
+
+/// Files that were already resolved on a previous run (so they won't go
+/// through the hashing/resolve pipeline again) but don't yet have a MyList
+/// entry, so `--add-to-mylist` can still register them without re-hashing.
+fn resolved_paths_missing_mylist(conn: &rusqlite::Connection) -> Result<Vec<(String, u32)>> {
+    let mut stmt = conn.prepare(
+        "SELECT path, fid FROM indexed_files
+         WHERE fid NOT IN (SELECT fid FROM mylist);",
+    )?;
+    let rows = stmt
+        .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(rows)
+}
+
+pub(crate) async fn index(
+    path: &Path,
+    db_path: &Path,
+    jobs: usize,
+    mylist: Option<MylistOptions>,
+) -> Result<()> {
+    let conn = init_database(db_path)?;
+    let done = already_done_paths(&conn)?;
+    let indexed = IndexedFileLookup::load(&conn)?;
+    let pending_mylist = if mylist.is_some() {
+        resolved_paths_missing_mylist(&conn)?
+    } else {
+        Vec::new()
+    };
+    let conn = std::sync::Arc::new(std::sync::Mutex::new(conn));
 
     let mpb = MultiProgress::new();
 
@@ -410,7 +737,6 @@ pub(crate) async fn index(path: &Path, db_path: &Path) -> Result<()> {
         .unwrap();
 
     let overall = mpb.add(ProgressBar::new(0));
-    // overall.enable_steady_tick(Duration::from_millis(125));
     overall.set_style(overall_style);
     overall.set_message("Building file list...");
 
@@ -421,8 +747,12 @@ pub(crate) async fn index(path: &Path, db_path: &Path) -> Result<()> {
             if path.is_dir() {
                 dirs.push(path);
             } else {
-                files.push(path);
                 overall.inc_length(1);
+                if done.contains(&path.to_string_lossy().to_string()) || indexed.contains(&path) {
+                    overall.inc(1);
+                } else {
+                    files.push(path);
+                }
             }
         }
     }
@@ -430,92 +760,828 @@ pub(crate) async fn index(path: &Path, db_path: &Path) -> Result<()> {
     overall.reset_eta();
     overall.set_message("Indexing files...");
 
-    for file_path in files {
-        let size = fs::metadata(&file_path).await.unwrap().len() / 1024;
-
-        let pb = mpb.insert_before(&overall, ProgressBar::new(size));
-        pb.enable_steady_tick(Duration::from_millis(125));
-        pb.set_style(file_style.clone());
-        pb.set_message(
-            file_path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
+    let jobs = jobs.max(1);
+
+    // Pulled from by every worker below, rather than split into one static
+    // chunk per worker up front: an anime library's file sizes vary wildly
+    // (23-minute episodes next to movie-length specials), so a fixed split
+    // leaves some workers idle while others are still grinding through their
+    // share of the big files.
+    let work_queue = std::sync::Arc::new(std::sync::Mutex::new(files));
+
+    // Hashing is CPU/IO-bound, so it runs on a pool of blocking workers, each
+    // pushing finished hashes into a single queue that feeds the one AniDB
+    // session below. This keeps the UDP lookups serialized while letting
+    // ed2k_hash saturate every core.
+    let (hashed_tx, mut hashed_rx) = tokio::sync::mpsc::channel::<HashedFile>(jobs * 2);
+
+    let mut hash_handles = Vec::new();
+    for _ in 0..jobs {
+        let tx = hashed_tx.clone();
+        let conn = conn.clone();
+        let mpb = mpb.clone();
+        let overall = overall.clone();
+        let file_style = file_style.clone();
+        let work_queue = work_queue.clone();
+
+        hash_handles.push(tokio::task::spawn_blocking(move || {
+            loop {
+                let file_path = match work_queue.lock().unwrap().pop() {
+                    Some(file_path) => file_path,
+                    None => break,
+                };
+
+                let size = match File::open(&file_path).and_then(|f| f.metadata()) {
+                    Ok(meta) => meta.len(),
+                    Err(e) => {
+                        let conn = conn.lock().unwrap();
+                        set_file_status(&conn, &file_path, "errored", None, None, Some(&e.to_string())).ok();
+                        continue;
+                    }
+                };
+
+                let pb = mpb.insert_before(&overall, ProgressBar::new(size / 1024));
+                pb.enable_steady_tick(Duration::from_millis(125));
+                pb.set_style(file_style.clone());
+                pb.set_message(
+                    file_path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string(),
+                );
+
+                let file = File::open(&file_path).expect("opening file");
+
+                {
+                    let conn = conn.lock().unwrap();
+                    set_file_status(&conn, &file_path, "pending", Some(size as i64), None, None).ok();
+                }
+
+                match ed2k_hash(&file, &pb) {
+                    Ok(hash) => {
+                        let ed2k = format!("{:032x}", u128::from_be_bytes(hash));
+
+                        {
+                            let conn = conn.lock().unwrap();
+                            set_file_status(&conn, &file_path, "hashed", Some(size as i64), Some(&ed2k), None).ok();
+                        }
+
+                        if tx.blocking_send(HashedFile { path: file_path, size, ed2k, pb }).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        let conn = conn.lock().unwrap();
+                        set_file_status(&conn, &file_path, "errored", Some(size as i64), None, Some(&e.to_string())).ok();
+                        pb.finish_with_message("Hashing failed");
+                    }
+                }
+            }
+        }));
+    }
+    drop(hashed_tx);
+
+    let mut anidb = PacedAniDb::new(AniDb::new("tetsu", 1));
+
+    anidb
+        .auth("darkwater_", &std::env::var("PASS").unwrap())
+        .await
+        .expect("failed login");
+
+    log::info!("session key: {}", anidb.session_key().unwrap());
+
+    let mut backoff = MIN_ANIDB_REQUEST_INTERVAL;
+
+    while let Some(hashed) = hashed_rx.recv().await {
+        tokio::time::sleep(backoff).await;
+
+        let pb = hashed.pb;
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("[{elapsed_precise}] {spinner:.green} {wide_msg}")
+                .unwrap(),
         );
 
-        if let Some(file) = facade
-            .get_file(&file_path, &pb)
-            .await
-            .context("failed to get file")?
-        {
-            log::debug!("file: {:#?}", file);
-
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("[{elapsed_precise}] {spinner:.green} {wide_msg:.green}")
-                    .unwrap(),
+        let resolved = {
+            let mut facade = CachedFacade::new(&mut anidb, conn.clone());
+            facade
+                .resolve_and_cache(&hashed.path, hashed.size, &hashed.ed2k)
+                .await
+        };
+
+        match resolved {
+            Ok(Some(file)) => {
+                backoff = MIN_ANIDB_REQUEST_INTERVAL;
+
+                let anime = {
+                    let mut facade = CachedFacade::new(&mut anidb, conn.clone());
+                    facade.get_anime(file.aid).await
+                };
+                let anime_name = anime.as_ref().map(|a| a.romaji_name.as_str()).unwrap_or("Unknown");
+
+                let episode = {
+                    let mut facade = CachedFacade::new(&mut anidb, conn.clone());
+                    facade.get_episode(file.eid).await
+                };
+                let episode_number = episode.as_ref().map(|e| e.epno.as_str()).unwrap_or("??");
+
+                let group = {
+                    let mut facade = CachedFacade::new(&mut anidb, conn.clone());
+                    facade.get_group(file.gid).await
+                };
+                let group_name = group.as_ref().map(|g| g.name.as_str()).unwrap_or("Unknown");
+
+                {
+                    let conn = conn.lock().unwrap();
+                    set_file_status(&conn, &hashed.path, "resolved", Some(hashed.size as i64), Some(&hashed.ed2k), None).ok();
+                }
+
+                let mut message = format!("{anime_name} - {episode_number} [{group_name}]");
+
+                if let Some(opts) = &mylist {
+                    let mut facade = CachedFacade::new(&mut anidb, conn.clone());
+                    match facade
+                        .mylist_for_file(
+                            file.fid,
+                            opts.state.as_u8(),
+                            opts.watched,
+                            opts.storage.as_deref().unwrap_or(""),
+                            opts.source.as_deref().unwrap_or(""),
+                        )
+                        .await
+                    {
+                        Ok(entry) => message.push_str(&format!(" [mylist lid={}]", entry.lid)),
+                        Err(e) => {
+                            log::warn!("failed to add {} to mylist: {e:?}", hashed.path.display());
+                            message.push_str(" [mylist failed]");
+                        }
+                    }
+                }
+
+                pb.set_message(message);
+            }
+            Ok(None) => {
+                backoff = MIN_ANIDB_REQUEST_INTERVAL;
+
+                let conn = conn.lock().unwrap();
+                set_file_status(&conn, &hashed.path, "not_found", Some(hashed.size as i64), Some(&hashed.ed2k), None).ok();
+
+                pb.set_message(format!("Not found: {}", hashed.path.display()));
+            }
+            Err(e) => {
+                backoff = (backoff * 2).min(MAX_ANIDB_BACKOFF);
+                log::warn!("failed to resolve {}: {e:?} (backing off to {backoff:?})", hashed.path.display());
+
+                let conn = conn.lock().unwrap();
+                set_file_status(&conn, &hashed.path, "errored", Some(hashed.size as i64), Some(&hashed.ed2k), Some(&e.to_string())).ok();
+
+                pb.set_message(format!("Error: {}", hashed.path.display()));
+            }
+        }
+
+        overall.inc(1);
+        pb.finish();
+    }
+
+    for handle in hash_handles {
+        if let Err(e) = handle.await {
+            log::error!("hashing worker panicked, its remaining files were not hashed: {e:?}");
+        }
+    }
+
+    overall.finish_with_message("Done!");
+
+    if let Some(opts) = &mylist {
+        if !pending_mylist.is_empty() {
+            log::info!(
+                "registering {} previously-indexed file(s) in mylist...",
+                pending_mylist.len()
             );
+        }
+
+        for (path, fid) in pending_mylist {
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                continue;
+            }
 
-            pb.set_message("Getting anime info...");
+            let mut facade = CachedFacade::new(&mut anidb, conn.clone());
+            match facade
+                .mylist_for_file(
+                    fid,
+                    opts.state.as_u8(),
+                    opts.watched,
+                    opts.storage.as_deref().unwrap_or(""),
+                    opts.source.as_deref().unwrap_or(""),
+                )
+                .await
+            {
+                Ok(entry) => log::info!("added {} to mylist (lid={})", path.display(), entry.lid),
+                Err(e) => log::warn!("failed to add {} to mylist: {e:?}", path.display()),
+            }
+        }
+    }
+
+    anidb.logout().await.expect("failed logout");
+
+    Ok(())
+}
 
-            let anime = facade.get_anime(file.aid).await;
-            log::debug!("anime: {:#?}", anime);
+#[derive(Debug, Serialize)]
+struct QueryResult {
+    fid: u32,
+    aid: Option<u32>,
+    eid: Option<u32>,
+    romaji_name: Option<String>,
+    english_name: Option<String>,
+    episode_number: Option<String>,
+    episode_title: Option<String>,
+    group_name: Option<String>,
+    group_short: Option<String>,
+    video_resolution: Option<String>,
+    source: Option<String>,
+    video_codec: Option<String>,
+    audio_codec_list: Option<String>,
+}
 
-            let anime_name = anime
-                .as_ref()
-                .map(|a| a.romaji_name.as_str())
-                .unwrap_or("Unknown");
+/// Look up a previously indexed file purely from the local SQLite cache, without
+/// contacting AniDB, and print the `files` -> `episodes`/`groups` -> `anime` record
+/// it resolved to.
+pub(crate) async fn query(path: &Path, db_path: &Path, json: bool) -> Result<()> {
+    let conn = init_database(db_path)?;
+
+    let result = conn
+        .query_row(
+            "SELECT
+                files.fid, files.aid, files.eid,
+                anime.romaji_name, anime.english_name,
+                episodes.epno, episodes.eng,
+                groups.name, groups.short,
+                files.video_resolution, files.source, files.video_codec, files.audio_codec_list
+            FROM indexed_files
+            JOIN files ON files.fid = indexed_files.fid
+            LEFT JOIN anime ON anime.aid = files.aid
+            LEFT JOIN episodes ON episodes.eid = files.eid
+            LEFT JOIN groups ON groups.gid = files.gid
+            WHERE indexed_files.path = ?
+               OR (indexed_files.filename = ? AND indexed_files.filesize = ?);",
+            params![&path.to_string_lossy(), &path.file_name().unwrap_or_default().to_string_lossy(), path_size(path)],
+            |row| {
+                Ok(QueryResult {
+                    fid: row.get(0)?,
+                    aid: row.get(1)?,
+                    eid: row.get(2)?,
+                    romaji_name: row.get(3)?,
+                    english_name: row.get(4)?,
+                    episode_number: row.get(5)?,
+                    episode_title: row.get(6)?,
+                    group_name: row.get(7)?,
+                    group_short: row.get(8)?,
+                    video_resolution: row.get(9)?,
+                    source: row.get(10)?,
+                    video_codec: row.get(11)?,
+                    audio_codec_list: row.get(12)?,
+                })
+            },
+        )
+        .optional()
+        .context("failed to query indexed file")?;
 
-            pb.set_message(anime_name.to_string());
+    let Some(result) = result else {
+        bail!("{} was never indexed", path.display());
+    };
 
-            let episode = facade.get_episode(file.eid).await;
-            log::debug!("episode: {:#?}", episode);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        println!(
+            "{} ({})",
+            result.romaji_name.as_deref().unwrap_or("Unknown anime"),
+            result.english_name.as_deref().unwrap_or("?"),
+        );
+        println!(
+            "  Episode {}: {}",
+            result.episode_number.as_deref().unwrap_or("??"),
+            result.episode_title.as_deref().unwrap_or("Unknown title"),
+        );
+        println!(
+            "  Group: {} ({})",
+            result.group_name.as_deref().unwrap_or("Unknown"),
+            result.group_short.as_deref().unwrap_or("?"),
+        );
+        println!(
+            "  {} / {} / video: {} / audio: {}",
+            result.video_resolution.as_deref().unwrap_or("?"),
+            result.source.as_deref().unwrap_or("?"),
+            result.video_codec.as_deref().unwrap_or("?"),
+            result.audio_codec_list.as_deref().unwrap_or("?"),
+        );
+        println!(
+            "  fid={} aid={} eid={}",
+            result.fid,
+            result.aid.map(|v| v.to_string()).unwrap_or_else(|| "?".into()),
+            result.eid.map(|v| v.to_string()).unwrap_or_else(|| "?".into()),
+        );
+    }
 
-            let episode_number = episode.as_ref().map(|e| e.epno.as_str()).unwrap_or("??");
+    Ok(())
+}
 
-            pb.set_message(format!("{anime_name} - {episode_number}"));
+/// Run SQLite's own integrity check, then look for problems specific to this schema:
+/// `indexed_files` rows whose path has vanished from disk, `indexed_files.fid` pointing
+/// at a `files` row that no longer exists, and `files` rows whose `aid`/`eid`/`gid`
+/// has no matching `anime`/`episodes`/`groups` row. Reports by default; pass
+/// `delete_orphan_rows` to actually clean them up.
+pub(crate) async fn check(db_path: &Path, delete_orphan_rows: bool, verify_hashes: bool) -> Result<()> {
+    let conn = init_database(db_path)?;
+
+    let mut integrity_stmt = conn.prepare("PRAGMA integrity_check;")?;
+    let integrity = integrity_stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(integrity_stmt);
+
+    if integrity.as_slice() == ["ok"] {
+        println!("PRAGMA integrity_check: ok");
+    } else {
+        println!("PRAGMA integrity_check reported problems:");
+        for problem in &integrity {
+            println!("  {problem}");
+        }
+    }
 
-            let group = facade.get_group(file.gid).await;
-            log::debug!("group: {:#?}", group);
+    let mut vanished_paths = 0;
+    let mut stmt = conn.prepare("SELECT path FROM indexed_files;")?;
+    let paths = stmt
+        .query_map(params![], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(stmt);
+
+    for path in paths {
+        if !Path::new(&path).exists() {
+            vanished_paths += 1;
+            if delete_orphan_rows {
+                log::info!("deleting vanished path from index: {path}");
+                conn.execute("DELETE FROM indexed_files WHERE path = ?;", [&path])?;
+            } else {
+                println!("vanished: {path} (no longer exists on disk)");
+            }
+        }
+    }
 
-            let group_name = group.as_ref().map(|g| g.name.as_str()).unwrap_or("Unknown");
+    let mut orphan_fid_stmt = conn.prepare(
+        "SELECT path, fid FROM indexed_files WHERE fid NOT IN (SELECT fid FROM files);",
+    )?;
+    let orphan_fids = orphan_fid_stmt
+        .query_map(params![], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(orphan_fid_stmt);
+
+    for (path, fid) in &orphan_fids {
+        if delete_orphan_rows {
+            log::info!("deleting indexed_files row with missing fid {fid}: {path}");
+            conn.execute("DELETE FROM indexed_files WHERE path = ?;", [path])?;
+        } else {
+            println!("orphan: {path} references missing files.fid {fid}");
+        }
+    }
 
-            pb.set_message(format!("{anime_name} - {episode_number} [{group_name}]"));
+    let mut orphan_files_stmt = conn.prepare(
+        "SELECT fid, aid, eid, gid FROM files
+         WHERE (aid IS NOT NULL AND aid NOT IN (SELECT aid FROM anime))
+            OR (eid IS NOT NULL AND eid NOT IN (SELECT eid FROM episodes))
+            OR (gid IS NOT NULL AND gid NOT IN (SELECT gid FROM groups));",
+    )?;
+    let orphan_files = orphan_files_stmt
+        .query_map(params![], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(orphan_files_stmt);
+
+    for (fid, aid, eid, gid) in &orphan_files {
+        if delete_orphan_rows {
+            log::info!("deleting files row {fid} with dangling aid/eid/gid");
+            conn.execute("DELETE FROM files WHERE fid = ?;", [fid])?;
         } else {
-            pb.set_style(
-                ProgressStyle::default_bar()
-                    .template("[{elapsed_precise}] {spinner:.green} {wide_msg:.yellow}")
-                    .unwrap(),
+            println!("orphan: files.fid {fid} has dangling aid={aid:?} eid={eid:?} gid={gid:?}");
+        }
+    }
+
+    let mut hash_mismatches = 0;
+    if verify_hashes {
+        let mut stmt = conn.prepare(
+            "SELECT indexed_files.path, files.size, files.ed2k
+             FROM indexed_files
+             JOIN files ON files.fid = indexed_files.fid;",
+        )?;
+        let rows = stmt
+            .query_map(params![], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(stmt);
+
+        let pb = ProgressBar::hidden();
+        for (path, stored_size, stored_ed2k) in rows {
+            let path = Path::new(&path);
+            let Ok(file) = File::open(path) else {
+                println!("modified: {} no longer opens", path.display());
+                hash_mismatches += 1;
+                continue;
+            };
+
+            let size = file.metadata().context("failed to stat file")?.len() as i64;
+            if size != stored_size {
+                println!(
+                    "modified: {} size changed ({stored_size} -> {size})",
+                    path.display()
+                );
+                hash_mismatches += 1;
+                continue;
+            }
+
+            let ed2k = format!(
+                "{:032x}",
+                u128::from_be_bytes(ed2k_hash(&file, &pb).context("failed to hash")?)
             );
+            if ed2k != stored_ed2k {
+                println!("modified: {} contents changed (ed2k mismatch)", path.display());
+                hash_mismatches += 1;
+            }
+        }
+    }
+
+    println!(
+        "check complete: {} vanished path(s), {} orphan fid(s), {} orphan anime/episode/group reference(s){}",
+        vanished_paths,
+        orphan_fids.len(),
+        orphan_files.len(),
+        if verify_hashes {
+            format!(", {hash_mismatches} modified file(s)")
+        } else {
+            String::new()
+        },
+    );
 
-            pb.set_message(format!("Not found: {}", file_path.display()));
+    Ok(())
+}
+
+/// Poll a file's size until it stops growing, so we don't hash a file that is
+/// still being written to. Returns `false` if the file disappeared while we
+/// were waiting for it to settle.
+async fn wait_for_stable_size(path: &Path) -> bool {
+    let Ok(meta) = fs::metadata(path).await else {
+        return false;
+    };
+    let mut last_size = meta.len();
+
+    loop {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let Ok(meta) = fs::metadata(path).await else {
+            return false;
+        };
+
+        if meta.len() == last_size {
+            return true;
         }
+        last_size = meta.len();
+    }
+}
 
-        overall.inc(1);
-        pb.finish();
+/// Keep a single authenticated AniDB session alive and incrementally index a
+/// folder as files are created, moved or removed, instead of requiring a
+/// manual re-run of `index`.
+pub(crate) async fn watch(path: &Path, db_path: &Path) -> Result<()> {
+    let conn = std::sync::Arc::new(std::sync::Mutex::new(init_database(db_path)?));
+
+    let mut anidb = PacedAniDb::new(AniDb::new("tetsu", 1));
+    anidb
+        .auth("darkwater_", &std::env::var("PASS").unwrap())
+        .await
+        .expect("failed login");
+
+    log::info!("session key: {}", anidb.session_key().unwrap());
+
+    let mut facade = CachedFacade::new(&mut anidb, conn.clone());
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher: RecommendedWatcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        match res {
+            Ok(event) => {
+                let _ = tx.send(event);
+            }
+            Err(e) => log::warn!("watch error: {:?}", e),
+        }
+    })?;
+    watcher.watch(path, RecursiveMode::Recursive)?;
+
+    log::info!("watching {} for changes...", path.display());
+
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+
+    loop {
+        match tokio::time::timeout(Duration::from_secs(1), rx.recv()).await {
+            Ok(Some(event)) => match event.kind {
+                EventKind::Remove(_) => {
+                    for removed in event.paths {
+                        pending.remove(&removed);
+                        log::info!("removing from index: {}", removed.display());
+                        facade
+                            .conn
+                            .lock()
+                            .unwrap()
+                            .execute(
+                                "DELETE FROM indexed_files WHERE path = ?;",
+                                [removed.to_string_lossy()],
+                            )
+                            .ok();
+                    }
+                }
+                EventKind::Create(_) | EventKind::Modify(_) => {
+                    for changed in event.paths {
+                        if changed.is_file() {
+                            pending.insert(changed, Instant::now());
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Ok(None) => break,
+            Err(_) => {}
+        }
+
+        let now = Instant::now();
+        let ready: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, seen_at)| now.duration_since(**seen_at) >= WATCH_DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in ready {
+            pending.remove(&path);
+
+            if !wait_for_stable_size(&path).await {
+                log::debug!("{} disappeared before it stabilized", path.display());
+                continue;
+            }
+
+            let pb = ProgressBar::hidden();
+            let last_request_before = facade.anidb.last_request;
+            match facade.get_file(&path, &pb).await {
+                Ok(Some(file)) => log::info!("indexed {} as fid {}", path.display(), file.fid),
+                Ok(None) => log::info!("not found on anidb: {}", path.display()),
+                Err(e) => log::warn!("failed to index {}: {:?}", path.display(), e),
+            }
+
+            if facade.anidb.last_request != last_request_before {
+                // The UDP API bans clients that send packets too fast, so pace
+                // lookups even though they're already serialized through one
+                // session. PacedAniDb already paced the call itself; this
+                // extra margin only applies after a real lookup, not a cache hit.
+                tokio::time::sleep(MIN_ANIDB_REQUEST_INTERVAL).await;
+            }
+        }
     }
 
-    overall.finish_with_message("Done!");
+    Ok(())
+}
+
+/// How `organize` places a resolved file into the target library layout.
+#[derive(Debug, Clone, ValueEnum)]
+pub(crate) enum OrganizeMode {
+    /// Move the file, updating `indexed_files.path` so the cache stays valid.
+    Rename,
+    /// Hardlink the file into place, leaving the original where it is.
+    Hardlink,
+    /// Symlink the file into place, leaving the original where it is.
+    Symlink,
+}
+
+fn sanitize_filename(name: &str) -> String {
+    name.chars()
+        .map(|c| if "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect()
+}
+
+struct OrganizeRow {
+    path: String,
+    romaji_name: Option<String>,
+    english_name: Option<String>,
+    epno: Option<String>,
+    group_name: Option<String>,
+    group_short: Option<String>,
+    video_resolution: Option<String>,
+    source: Option<String>,
+}
+
+fn render_template(template: &str, row: &OrganizeRow) -> String {
+    template
+        .replace("{romaji_name}", row.romaji_name.as_deref().unwrap_or("Unknown"))
+        .replace("{english_name}", row.english_name.as_deref().unwrap_or("Unknown"))
+        .replace("{epno}", row.epno.as_deref().unwrap_or("??"))
+        .replace("{group_name}", row.group_name.as_deref().unwrap_or("Unknown"))
+        .replace("{group_short}", row.group_short.as_deref().unwrap_or("?"))
+        .replace("{video_resolution}", row.video_resolution.as_deref().unwrap_or("?"))
+        .replace("{source}", row.source.as_deref().unwrap_or("?"))
+}
+
+/// Whether `a` and `b` are the same file on disk, so a hardlink that already
+/// exists at the destination can be treated as "already organized" rather
+/// than a collision.
+fn same_file(a: &Path, b: &Path) -> bool {
+    let (Ok(ma), Ok(mb)) = (a.metadata(), b.metadata()) else {
+        return false;
+    };
 
+    #[cfg(unix)]
     {
-        let mut stmt = conn.prepare("SELECT path FROM indexed_files;").unwrap();
-        let indexed_files = stmt
-            .query_map(params![], |row| row.get::<_, String>(0))
-            .unwrap();
-
-        let mut del_stmt = conn
-            .prepare("DELETE FROM indexed_files WHERE path = ?;")
-            .unwrap();
-        for path in indexed_files {
-            let path = path.unwrap();
-            if !Path::new(path.as_str()).exists() {
-                log::info!("deleting {} from index", path);
-                del_stmt.execute([path]).unwrap();
+        use std::os::unix::fs::MetadataExt;
+        ma.dev() == mb.dev() && ma.ino() == mb.ino()
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        ma.file_index() == mb.file_index() && ma.volume_serial_number() == mb.volume_serial_number()
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    false
+}
+
+/// Materialize every resolved file in the index into `target`, named after
+/// `template`, by renaming, hardlinking or symlinking it depending on `mode`.
+pub(crate) async fn organize(
+    db_path: &Path,
+    target: &Path,
+    template: &str,
+    mode: OrganizeMode,
+    dry_run: bool,
+) -> Result<()> {
+    let conn = init_database(db_path)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT
+            indexed_files.path,
+            anime.romaji_name, anime.english_name,
+            episodes.epno,
+            groups.name, groups.short,
+            files.video_resolution, files.source
+        FROM indexed_files
+        JOIN files ON files.fid = indexed_files.fid
+        LEFT JOIN anime ON anime.aid = files.aid
+        LEFT JOIN episodes ON episodes.eid = files.eid
+        LEFT JOIN groups ON groups.gid = files.gid;",
+    )?;
+
+    let rows = stmt
+        .query_map(params![], |row| {
+            Ok(OrganizeRow {
+                path: row.get(0)?,
+                romaji_name: row.get(1)?,
+                english_name: row.get(2)?,
+                epno: row.get(3)?,
+                group_name: row.get(4)?,
+                group_short: row.get(5)?,
+                video_resolution: row.get(6)?,
+                source: row.get(7)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    drop(stmt);
+
+    if !dry_run {
+        fs::create_dir_all(target)
+            .await
+            .context("failed to create target directory")?;
+    }
+
+    for row in rows {
+        let src = PathBuf::from(&row.path);
+        if !src.exists() {
+            log::warn!("skipping {}: no longer exists on disk", src.display());
+            continue;
+        }
+
+        let name = sanitize_filename(&render_template(template, &row));
+        let dest = match src.extension().and_then(|e| e.to_str()) {
+            Some(ext) => target.join(format!("{name}.{ext}")),
+            None => target.join(name),
+        };
+
+        if dry_run {
+            println!("{} -> {}", src.display(), dest.display());
+            continue;
+        }
+
+        if dest.exists() {
+            let already_organized = match mode {
+                OrganizeMode::Rename => false,
+                OrganizeMode::Hardlink => same_file(&src, &dest),
+                OrganizeMode::Symlink => std::fs::read_link(&dest).is_ok_and(|l| l == src),
+            };
+
+            if already_organized {
+                log::debug!("{} already organized at {}", src.display(), dest.display());
+            } else {
+                log::warn!(
+                    "skipping {}: destination {} already exists",
+                    src.display(),
+                    dest.display()
+                );
+            }
+
+            continue;
+        }
+
+        match mode {
+            OrganizeMode::Rename => {
+                std::fs::rename(&src, &dest)
+                    .with_context(|| format!("failed to rename {}", src.display()))?;
+                conn.execute(
+                    "UPDATE indexed_files SET path = ? WHERE path = ?",
+                    params![dest.to_string_lossy(), &row.path],
+                )?;
+            }
+            OrganizeMode::Hardlink => {
+                std::fs::hard_link(&src, &dest)
+                    .with_context(|| format!("failed to hardlink {}", src.display()))?;
+            }
+            OrganizeMode::Symlink => {
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(&src, &dest)
+                    .with_context(|| format!("failed to symlink {}", src.display()))?;
+
+                #[cfg(windows)]
+                std::os::windows::fs::symlink_file(&src, &dest)
+                    .with_context(|| format!("failed to symlink {}", src.display()))?;
             }
         }
+
+        println!("{} -> {}", src.display(), dest.display());
     }
 
+    Ok(())
+}
+
+/// Resolve `path` (indexing it first if necessary) and register it in AniDB
+/// MyList, reusing the cached entry if it was already added on a previous run.
+pub(crate) async fn mylist(
+    path: &Path,
+    db_path: &Path,
+    state: MyListState,
+    watched: bool,
+    storage: Option<String>,
+    source: Option<String>,
+) -> Result<()> {
+    let conn = std::sync::Arc::new(std::sync::Mutex::new(init_database(db_path)?));
+
+    let mut anidb = PacedAniDb::new(AniDb::new("tetsu", 1));
+    anidb
+        .auth("darkwater_", &std::env::var("PASS").unwrap())
+        .await
+        .expect("failed login");
+
+    log::info!("session key: {}", anidb.session_key().unwrap());
+
+    let mut facade = CachedFacade::new(&mut anidb, conn.clone());
+
+    let pb = ProgressBar::hidden();
+    let Some(file) = facade
+        .get_file(path, &pb)
+        .await
+        .context("failed to get file")?
+    else {
+        bail!("{} could not be resolved on AniDB", path.display());
+    };
+
+    let entry = facade
+        .mylist_for_file(
+            file.fid,
+            state.as_u8(),
+            watched,
+            storage.as_deref().unwrap_or(""),
+            source.as_deref().unwrap_or(""),
+        )
+        .await?;
+
+    println!(
+        "mylist: lid={} fid={} state={} viewdate={}",
+        entry.lid, entry.fid, entry.state, entry.viewdate
+    );
+
     anidb.logout().await.expect("failed logout");
 
     Ok(())
@@ -23,12 +23,98 @@ enum Action {
     Index {
         /// Folder to index
         path: PathBuf,
+
+        /// Number of files to hash concurrently
+        #[clap(short, long, default_value_t = 1)]
+        jobs: usize,
+
+        /// Register every resolved file in AniDB MyList
+        #[clap(long)]
+        add_to_mylist: bool,
+
+        /// MyList storage state to submit for newly added entries
+        #[clap(long, value_enum, default_value = "internal")]
+        mylist_state: indexer::MyListState,
+
+        /// Mark newly added MyList entries as watched
+        #[clap(long)]
+        mylist_watched: bool,
+
+        /// Free-form storage note to submit with newly added MyList entries
+        #[clap(long)]
+        mylist_storage: Option<String>,
+
+        /// Free-form source note to submit with newly added MyList entries
+        #[clap(long)]
+        mylist_source: Option<String>,
     },
 
     /// Get information about a file that was previously indexed
     Query {
         /// File to query
         path: PathBuf,
+
+        /// Print the result as JSON instead of a human-readable summary
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Keep watching a folder and incrementally index files as they change
+    Watch {
+        /// Folder to watch
+        path: PathBuf,
+    },
+
+    /// Audit the SQLite index for integrity problems
+    Check {
+        /// Actually remove orphaned rows instead of just reporting them
+        #[clap(long)]
+        delete_orphan_rows: bool,
+
+        /// Re-hash every indexed file and flag ones that no longer match the cache
+        #[clap(long)]
+        verify_hashes: bool,
+    },
+
+    /// Materialize resolved files into a templated library layout
+    Organize {
+        /// Directory to organize files into
+        target: PathBuf,
+
+        /// Filename template. Supports {romaji_name}, {english_name}, {epno},
+        /// {group_name}, {group_short}, {video_resolution}, {source}
+        #[clap(long, default_value = "{romaji_name} - {epno} [{group_short}]")]
+        template: String,
+
+        /// How to place files into the target directory
+        #[clap(long, value_enum, default_value = "hardlink")]
+        mode: indexer::OrganizeMode,
+
+        /// Print planned moves without touching the filesystem
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    /// Register a file in AniDB MyList, indexing it first if needed
+    Mylist {
+        /// File to register
+        path: PathBuf,
+
+        /// MyList storage state to submit
+        #[clap(long, value_enum, default_value = "internal")]
+        state: indexer::MyListState,
+
+        /// Mark the entry as watched
+        #[clap(long)]
+        watched: bool,
+
+        /// Free-form storage note
+        #[clap(long)]
+        storage: Option<String>,
+
+        /// Free-form source note
+        #[clap(long)]
+        source: Option<String>,
     },
 }
 
@@ -43,10 +129,53 @@ async fn main() -> Result<()> {
     });
 
     match args.action {
-        Action::Index { path } => {
-            indexer::index(&path, &db_path).await?;
+        Action::Index {
+            path,
+            jobs,
+            add_to_mylist,
+            mylist_state,
+            mylist_watched,
+            mylist_storage,
+            mylist_source,
+        } => {
+            let mylist = add_to_mylist.then_some(indexer::MylistOptions {
+                state: mylist_state,
+                watched: mylist_watched,
+                storage: mylist_storage,
+                source: mylist_source,
+            });
+
+            indexer::index(&path, &db_path, jobs, mylist).await?;
+        }
+        Action::Query { path, json } => {
+            indexer::query(&path, &db_path, json).await?;
+        }
+        Action::Watch { path } => {
+            indexer::watch(&path, &db_path).await?;
+        }
+        Action::Check {
+            delete_orphan_rows,
+            verify_hashes,
+        } => {
+            indexer::check(&db_path, delete_orphan_rows, verify_hashes).await?;
+        }
+        Action::Organize {
+            target,
+            template,
+            mode,
+            dry_run,
+        } => {
+            indexer::organize(&db_path, &target, &template, mode, dry_run).await?;
+        }
+        Action::Mylist {
+            path,
+            state,
+            watched,
+            storage,
+            source,
+        } => {
+            indexer::mylist(&path, &db_path, state, watched, storage, source).await?;
         }
-        Action::Query { path: _ } => unimplemented!(),
     }
 
     Ok(())